@@ -0,0 +1,488 @@
+/*
+* Copyright 2018-2020 TON DEV SOLUTIONS LTD.
+*
+* Licensed under the SOFTWARE EVALUATION License (the "License"); you may not use
+* this file except in compliance with the License.
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific TON DEV software governing permissions and
+* limitations under the License.
+*/
+
+//! Typed Rust binding generator.
+//!
+//! Given a parsed contract ABI (its functions/events and their `Param`
+//! lists) this emits a Rust module exposing one struct per contract and one
+//! method per function, built on native Rust types instead of generic
+//! `Token` values - modelled on the native-contract generators used
+//! elsewhere in the TON tooling.
+//!
+//! Generated `encode`/`decode` bodies are built field-by-field on top of
+//! [`AbiValue`], a small wire codec implemented here for the scalar types
+//! `rust_type` maps to. A function whose inputs/outputs are all wire-coded
+//! gets a real `encode`/`decode`; one that uses a type `AbiValue` does not
+//! cover (see `is_wire_supported`: e.g. addresses, cells, arrays, maps,
+//! tuples, integers other than 8/16/32/64/128 bits) gets an honest
+//! `Err(AbiCodecError::Unsupported(..))` instead of a body that cannot
+//! run - never a panic.
+
+use std::fmt::Write as _;
+
+use crate::{Param, ParamType};
+
+/// A function or event this generator emits a method/struct for.
+pub struct AbiFunction {
+    pub name: String,
+    pub inputs: Vec<Param>,
+    pub outputs: Vec<Param>,
+}
+
+/// A contract's parsed ABI, as consumed by `generate`.
+pub struct AbiContract {
+    pub name: String,
+    pub functions: Vec<AbiFunction>,
+}
+
+/// Raised when a `ParamType` has no native Rust mapping, so generation
+/// fails loudly instead of emitting code that cannot compile.
+#[derive(Debug)]
+pub struct UnsupportedType {
+    pub function: String,
+    pub param_type: ParamType,
+}
+
+impl std::fmt::Display for UnsupportedType {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "no Rust mapping for `{}` used by `{}`", self.param_type, self.function)
+    }
+}
+
+impl std::error::Error for UnsupportedType {}
+
+/// Error produced at runtime by a generated binding's `encode`/`decode`.
+#[derive(Debug)]
+pub enum AbiCodecError {
+    /// Not enough bytes remained to decode the next field.
+    UnexpectedEof,
+    /// A decoded length prefix ran past the end of the buffer, or the
+    /// bytes of a decoded `String` were not valid UTF-8.
+    InvalidLength,
+    /// A field's `ParamType` is not wired to the wire codec yet (see
+    /// `is_wire_supported`); the message names the function and field.
+    Unsupported(String),
+}
+
+impl std::fmt::Display for AbiCodecError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            AbiCodecError::UnexpectedEof => write!(f, "unexpected end of data"),
+            AbiCodecError::InvalidLength => write!(f, "invalid length prefix"),
+            AbiCodecError::Unsupported(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl std::error::Error for AbiCodecError {}
+
+/// Encodes/decodes a single generated-binding field to/from the flat byte
+/// wire format generated methods use: fixed-width integers are packed
+/// big-endian, `bool` as one byte, and variable-length data (`Vec<u8>`,
+/// `String`) is prefixed with a big-endian `u32` length.
+pub trait AbiValue: Sized {
+    fn abi_encode(&self, out: &mut Vec<u8>);
+    fn abi_decode(data: &[u8], pos: &mut usize) -> Result<Self, AbiCodecError>;
+}
+
+macro_rules! impl_abi_value_for_int {
+    ($($ty:ty),*) => {
+        $(
+            impl AbiValue for $ty {
+                fn abi_encode(&self, out: &mut Vec<u8>) {
+                    out.extend_from_slice(&self.to_be_bytes());
+                }
+
+                fn abi_decode(data: &[u8], pos: &mut usize) -> Result<Self, AbiCodecError> {
+                    let size = std::mem::size_of::<$ty>();
+                    let end = pos.checked_add(size).ok_or(AbiCodecError::UnexpectedEof)?;
+                    let bytes = data.get(*pos..end).ok_or(AbiCodecError::UnexpectedEof)?;
+                    *pos = end;
+                    Ok(<$ty>::from_be_bytes(bytes.try_into().expect("slice has exactly `size` bytes")))
+                }
+            }
+        )*
+    }
+}
+
+impl_abi_value_for_int!(u8, u16, u32, u64, u128, i8, i16, i32, i64, i128);
+
+impl AbiValue for bool {
+    fn abi_encode(&self, out: &mut Vec<u8>) {
+        out.push(if *self { 1 } else { 0 });
+    }
+
+    fn abi_decode(data: &[u8], pos: &mut usize) -> Result<Self, AbiCodecError> {
+        let byte = *data.get(*pos).ok_or(AbiCodecError::UnexpectedEof)?;
+        *pos += 1;
+        Ok(byte != 0)
+    }
+}
+
+impl AbiValue for Vec<u8> {
+    fn abi_encode(&self, out: &mut Vec<u8>) {
+        (self.len() as u32).abi_encode(out);
+        out.extend_from_slice(self);
+    }
+
+    fn abi_decode(data: &[u8], pos: &mut usize) -> Result<Self, AbiCodecError> {
+        let len = u32::abi_decode(data, pos)? as usize;
+        let end = pos.checked_add(len).ok_or(AbiCodecError::InvalidLength)?;
+        let bytes = data.get(*pos..end).ok_or(AbiCodecError::InvalidLength)?;
+        *pos = end;
+        Ok(bytes.to_vec())
+    }
+}
+
+impl AbiValue for String {
+    fn abi_encode(&self, out: &mut Vec<u8>) {
+        self.as_bytes().to_vec().abi_encode(out);
+    }
+
+    fn abi_decode(data: &[u8], pos: &mut usize) -> Result<Self, AbiCodecError> {
+        let bytes = Vec::<u8>::abi_decode(data, pos)?;
+        String::from_utf8(bytes).map_err(|_| AbiCodecError::InvalidLength)
+    }
+}
+
+impl<T: AbiValue> AbiValue for Option<T> {
+    fn abi_encode(&self, out: &mut Vec<u8>) {
+        match self {
+            Some(value) => {
+                out.push(1);
+                value.abi_encode(out);
+            }
+            None => out.push(0),
+        }
+    }
+
+    fn abi_decode(data: &[u8], pos: &mut usize) -> Result<Self, AbiCodecError> {
+        let tag = *data.get(*pos).ok_or(AbiCodecError::UnexpectedEof)?;
+        *pos += 1;
+        if tag == 0 { Ok(None) } else { Ok(Some(T::abi_decode(data, pos)?)) }
+    }
+}
+
+/// Whether `kind` is wired to [`AbiValue`] and a generated method can
+/// encode/decode it for real. `Ref` is transparent to the wire (it only
+/// changes cell layout, not the mapped Rust type), so it defers to its
+/// inner type; `Optional`/`String`/`Bool`/`Bytes` and `Uint`/`Int` of
+/// exactly 8, 16, 32, 64 or 128 bits are covered. `VarUint`/`VarInt` (ABI
+/// 2.1+ variable length integers) report `false` on purpose: true TON wire
+/// encoding for them is length-prefixed rather than fixed-width, which
+/// `AbiValue` does not model - callers get `AbiCodecError::Unsupported`
+/// instead of a silently wrong fixed-width encoding. Every other type
+/// (`Address`, `Cell`, `FixedBytes`, `Gram`, `Time`, `Expire`,
+/// `PublicKey`, `Tuple`, `Array`, `FixedArray`, `Map`, `Unknown`, and
+/// integers of any other width) reports `false` as well.
+pub fn is_wire_supported(kind: &ParamType) -> bool {
+    match kind {
+        ParamType::Bool | ParamType::Bytes | ParamType::String => true,
+        ParamType::Uint(n) | ParamType::Int(n) => matches!(n, 8 | 16 | 32 | 64 | 128),
+        ParamType::Optional(inner) | ParamType::Ref(inner) => is_wire_supported(inner),
+        ParamType::VarUint(_) | ParamType::VarInt(_) => false,
+        _ => false,
+    }
+}
+
+/// Generates a Rust module for `contract`: one struct named after the
+/// contract plus one method per function, taking/returning the native
+/// types produced by `rust_type` instead of `Token`. Functions whose
+/// fields are all covered by [`is_wire_supported`] get a real
+/// `encode`/`decode` built on [`AbiValue`]; others get an honest
+/// `Err(AbiCodecError::Unsupported(..))` rather than a panic.
+pub fn generate(contract: &AbiContract) -> Result<String, UnsupportedType> {
+    let mut out = String::new();
+    let mut tuples = String::new();
+
+    let _ = writeln!(out, "pub struct {} {{", contract.name);
+    let _ = writeln!(out, "    pub address: ton_block::MsgAddressInt,");
+    let _ = writeln!(out, "}}");
+    let _ = writeln!(out);
+    let _ = writeln!(out, "impl {} {{", contract.name);
+
+    for function in &contract.functions {
+        let args = function.inputs.iter()
+            .map(|p| Ok(format!("{}: {}", p.name, rust_type(&function.name, &p.kind, &mut tuples)?)))
+            .collect::<Result<Vec<_>, UnsupportedType>>()?
+            .join(", ");
+
+        let ret = match function.outputs.as_slice() {
+            [] => "()".to_owned(),
+            [single] => rust_type(&function.name, &single.kind, &mut tuples)?,
+            many => {
+                let types = many.iter()
+                    .map(|p| rust_type(&function.name, &p.kind, &mut tuples))
+                    .collect::<Result<Vec<_>, UnsupportedType>>()?;
+                format!("({})", types.join(", "))
+            }
+        };
+
+        let arg_names = function.inputs.iter().map(|p| p.name.clone()).collect::<Vec<_>>().join(", ");
+        let _ = writeln!(
+            out,
+            "    pub fn {name}(&self, {args}) -> ton_abi::Result<{ret}> {{\n        let _request = Self::encode_{name}({arg_names})\n            .map_err(|err| failure::format_err!(\"{name}: {{}}\", err))?;\n        Err(failure::format_err!(\"{name}: generated binding does not implement contract calls yet; see encode_{name}/decode_{name} for the (de)serialization that is wired up\"))\n    }}",
+            name = function.name, args = args, ret = ret, arg_names = arg_names,
+        );
+
+        let _ = writeln!(out, "{}", encode_method(function, &args));
+        let _ = writeln!(out, "{}", decode_method(function, &mut tuples)?);
+    }
+
+    let _ = writeln!(out, "}}");
+
+    Ok(format!("{}\n{}", tuples, out))
+}
+
+/// Emits `{Name}::encode_{function}`: packs every input field with
+/// `AbiValue::abi_encode`, or - if any field's type isn't wired yet - a
+/// body that returns `Err(AbiCodecError::Unsupported(..))`. Takes the same
+/// arguments as the generated method itself (there is no separate request
+/// struct in this generator).
+fn encode_method(function: &AbiFunction, args: &str) -> String {
+    if let Some(unsupported) = function.inputs.iter().find(|p| !is_wire_supported(&p.kind)) {
+        return format!(
+            "    pub fn encode_{name}({args}) -> Result<Vec<u8>, ton_abi::codegen::AbiCodecError> {{\n        Err(ton_abi::codegen::AbiCodecError::Unsupported(\"{name}.{field}: `{ty}` is not wired to the wire codec yet\".to_owned()))\n    }}",
+            name = function.name, args = args, field = unsupported.name, ty = unsupported.kind.type_signature(),
+        );
+    }
+
+    let mut body = String::new();
+    for input in &function.inputs {
+        let _ = writeln!(body, "        {}.abi_encode(&mut buf);", input.name);
+    }
+
+    format!(
+        "    pub fn encode_{name}({args}) -> Result<Vec<u8>, ton_abi::codegen::AbiCodecError> {{\n        let mut buf = Vec::new();\n{body}        Ok(buf)\n    }}",
+        name = function.name, args = args, body = body,
+    )
+}
+
+/// Emits `{Name}::decode_{function}`: reads every output field in order with
+/// `AbiValue::abi_decode`, or an honest `Err` if one isn't wired yet. The
+/// return type is built from `rust_type`, same as the main method's `ret` -
+/// the field *names* only ever appear in the `Ok((...))` body.
+fn decode_method(function: &AbiFunction, tuples: &mut String) -> Result<String, UnsupportedType> {
+    if let Some(unsupported) = function.outputs.iter().find(|p| !is_wire_supported(&p.kind)) {
+        // `generate` has already emitted any tuple structs for these
+        // outputs while building `ret`, so map them into a scratch buffer.
+        let types = function.outputs.iter()
+            .map(|p| rust_type(&function.name, &p.kind, &mut String::new()))
+            .collect::<Result<Vec<_>, UnsupportedType>>()?
+            .join(", ");
+        return Ok(format!(
+            "    pub fn decode_{name}(_data: &[u8]) -> Result<({types}), ton_abi::codegen::AbiCodecError> {{\n        Err(ton_abi::codegen::AbiCodecError::Unsupported(\"{name}.{field}: `{ty}` is not wired to the wire codec yet\".to_owned()))\n    }}",
+            name = function.name, types = types, field = unsupported.name, ty = unsupported.kind.type_signature(),
+        ));
+    }
+
+    let mut body = String::new();
+    for output in &function.outputs {
+        let _ = writeln!(
+            body,
+            "        let {name} = AbiValue::abi_decode(data, &mut pos)?;",
+            name = output.name,
+        );
+    }
+    let names = function.outputs.iter().map(|p| p.name.clone()).collect::<Vec<_>>().join(", ");
+    let types = function.outputs.iter()
+        .map(|p| rust_type(&function.name, &p.kind, tuples))
+        .collect::<Result<Vec<_>, UnsupportedType>>()?
+        .join(", ");
+
+    Ok(format!(
+        "    pub fn decode_{name}(data: &[u8]) -> Result<({types}), ton_abi::codegen::AbiCodecError> {{\n        let mut pos = 0usize;\n{body}        Ok(({names}))\n    }}",
+        name = function.name, types = types, body = body, names = names,
+    ))
+}
+
+/// Maps a single `ParamType` to the smallest fitting native Rust type,
+/// emitting a generated sub-struct into `tuples` for `Tuple` params.
+pub fn rust_type(function: &str, kind: &ParamType, tuples: &mut String) -> Result<String, UnsupportedType> {
+    let mapped = match kind {
+        ParamType::Bool => "bool".to_owned(),
+        ParamType::Uint(n) => uint_type(*n),
+        ParamType::Int(n) => int_type(*n),
+        ParamType::Address => "ton_block::MsgAddressInt".to_owned(),
+        ParamType::Bytes => "Vec<u8>".to_owned(),
+        ParamType::FixedBytes(n) => format!("[u8; {}]", n),
+        ParamType::Cell => "ton_types::Cell".to_owned(),
+        ParamType::Gram => "u128".to_owned(),
+        ParamType::Time | ParamType::Expire => "u64".to_owned(),
+        ParamType::PublicKey => "Option<ed25519_dalek::PublicKey>".to_owned(),
+        ParamType::String => "String".to_owned(),
+        ParamType::VarUint(size) => uint_type(size * 8),
+        ParamType::VarInt(size) => int_type(size * 8),
+        ParamType::Optional(inner) => format!("Option<{}>", rust_type(function, inner, tuples)?),
+        ParamType::Ref(inner) => rust_type(function, inner, tuples)?,
+        ParamType::Array(inner) => format!("Vec<{}>", rust_type(function, inner, tuples)?),
+        ParamType::FixedArray(inner, size) => format!("[{}; {}]", rust_type(function, inner, tuples)?, size),
+        ParamType::Map(key, value) => format!(
+            "std::collections::BTreeMap<{}, {}>",
+            rust_type(function, key, tuples)?,
+            rust_type(function, value, tuples)?,
+        ),
+        ParamType::Tuple(params) => {
+            let struct_name = tuple_struct_name(function, params);
+            let fields = params.iter()
+                .map(|p| Ok(format!("    pub {}: {},", p.name, rust_type(function, &p.kind, tuples)?)))
+                .collect::<Result<Vec<_>, UnsupportedType>>()?
+                .join("\n");
+            let _ = writeln!(tuples, "pub struct {} {{\n{}\n}}\n", struct_name, fields);
+            struct_name
+        }
+        ParamType::Unknown => return Err(UnsupportedType {
+            function: function.to_owned(),
+            param_type: kind.clone(),
+        }),
+    };
+    Ok(mapped)
+}
+
+fn tuple_struct_name(function: &str, params: &[Param]) -> String {
+    let seed = params.first().map(|p| p.name.as_str()).unwrap_or("value");
+    format!("{}{}", capitalize(function), capitalize(seed))
+}
+
+fn capitalize(name: &str) -> String {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().collect::<String>() + chars.as_str(),
+        None => String::new(),
+    }
+}
+
+fn uint_type(bits: usize) -> String {
+    match bits {
+        0..=8 => "u8".to_owned(),
+        9..=16 => "u16".to_owned(),
+        17..=32 => "u32".to_owned(),
+        33..=64 => "u64".to_owned(),
+        65..=128 => "u128".to_owned(),
+        _ => "num_bigint::BigUint".to_owned(),
+    }
+}
+
+fn int_type(bits: usize) -> String {
+    match bits {
+        0..=8 => "i8".to_owned(),
+        9..=16 => "i16".to_owned(),
+        17..=32 => "i32".to_owned(),
+        33..=64 => "i64".to_owned(),
+        65..=128 => "i128".to_owned(),
+        _ => "num_bigint::BigInt".to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn param(name: &str, kind: ParamType) -> Param {
+        Param { name: name.to_owned(), kind }
+    }
+
+    #[test]
+    fn abi_value_round_trips_scalars() {
+        let mut buf = Vec::new();
+        42u32.abi_encode(&mut buf);
+        true.abi_encode(&mut buf);
+        "hi".to_owned().abi_encode(&mut buf);
+        Some(7u8).abi_encode(&mut buf);
+
+        let mut pos = 0;
+        assert_eq!(u32::abi_decode(&buf, &mut pos).unwrap(), 42u32);
+        assert_eq!(bool::abi_decode(&buf, &mut pos).unwrap(), true);
+        assert_eq!(String::abi_decode(&buf, &mut pos).unwrap(), "hi".to_owned());
+        assert_eq!(Option::<u8>::abi_decode(&buf, &mut pos).unwrap(), Some(7u8));
+        assert_eq!(pos, buf.len());
+    }
+
+    #[test]
+    fn abi_decode_reports_unexpected_eof() {
+        let mut pos = 0;
+        assert!(matches!(u32::abi_decode(&[0u8, 1], &mut pos), Err(AbiCodecError::UnexpectedEof)));
+    }
+
+    #[test]
+    fn wire_support_covers_optional_and_ref_of_scalars() {
+        assert!(is_wire_supported(&ParamType::Optional(Box::new(ParamType::String))));
+        assert!(is_wire_supported(&ParamType::Ref(Box::new(ParamType::Uint(64)))));
+    }
+
+    #[test]
+    fn wire_support_honestly_excludes_variable_length_integers() {
+        // ABI 2.1's varuint/varint are length-prefixed on the wire, which
+        // `AbiValue` does not model; reporting `true` here would make
+        // generated code silently mis-encode these fields.
+        assert!(!is_wire_supported(&ParamType::VarUint(16)));
+        assert!(!is_wire_supported(&ParamType::VarInt(32)));
+    }
+
+    #[test]
+    fn generate_emits_real_encode_for_wire_supported_function() {
+        let contract = AbiContract {
+            name: "Wallet".to_owned(),
+            functions: vec![AbiFunction {
+                name: "transfer".to_owned(),
+                inputs: vec![param("amount", ParamType::Uint(64)), param("comment", ParamType::String)],
+                outputs: vec![param("ok", ParamType::Bool)],
+            }],
+        };
+
+        let code = generate(&contract).unwrap();
+        assert!(code.contains("fn encode_transfer"));
+        assert!(code.contains("amount.abi_encode(&mut buf);"));
+        assert!(code.contains("fn decode_transfer"));
+        assert!(!code.contains("unimplemented!()"));
+        assert!(!code.contains("AbiCodecError::Unsupported"));
+    }
+
+    #[test]
+    fn generate_emits_decode_return_type_not_field_names() {
+        // Regression test: `decode_method` once reused the *names* of the
+        // outputs (e.g. `ok`) as the return type instead of their mapped
+        // Rust types, producing `Result<(ok), ..>` - a reference to an
+        // undeclared identifier rather than a type. Pin the actual mapped
+        // types in the signature so the name/type mixup can't creep back.
+        let contract = AbiContract {
+            name: "Wallet".to_owned(),
+            functions: vec![AbiFunction {
+                name: "transfer".to_owned(),
+                inputs: vec![],
+                outputs: vec![param("ok", ParamType::Bool), param("balance", ParamType::Uint(64))],
+            }],
+        };
+
+        let code = generate(&contract).unwrap();
+        assert!(code.contains("fn decode_transfer(data: &[u8]) -> Result<(bool, u64), ton_abi::codegen::AbiCodecError>"));
+        assert!(!code.contains("Result<(ok, balance)"));
+    }
+
+    #[test]
+    fn generate_emits_honest_error_for_unsupported_field() {
+        let contract = AbiContract {
+            name: "Wallet".to_owned(),
+            functions: vec![AbiFunction {
+                name: "send".to_owned(),
+                inputs: vec![param("to", ParamType::Address)],
+                outputs: vec![],
+            }],
+        };
+
+        let code = generate(&contract).unwrap();
+        assert!(code.contains("AbiCodecError::Unsupported"));
+        assert!(!code.contains("unimplemented!()"));
+        assert!(!code.contains("todo!()"));
+    }
+}
@@ -14,6 +14,7 @@
 //! Function and event param types.
 
 use std::fmt;
+use std::str::FromStr;
 use Param;
 
 use crate::AbiError;
@@ -53,7 +54,22 @@ pub enum ParamType {
     /// Message expiration time
     Expire,
     /// Public key
-    PublicKey
+    PublicKey,
+    /// string: dynamic length UTF-8 string (ABI 2.1+).
+    String,
+    /// optional(T): value of type T that may be absent (ABI 2.1+).
+    Optional(Box<ParamType>),
+    /// ref(T): value of type T stored in a separate cell reference (ABI 2.1+).
+    Ref(Box<ParamType>),
+    /// varuint<N>: variable length unsigned integer fitting in N bytes
+    /// (ABI 2.1+). Signature, `bit_len` and version-gating are wired up;
+    /// the generated-binding wire codec (`ton_abi::codegen::AbiValue`)
+    /// does not encode/decode it yet - `codegen::is_wire_supported`
+    /// reports `false` for it rather than silently mis-encoding.
+    VarUint(usize),
+    /// varint<N>: variable length signed integer fitting in N bytes
+    /// (ABI 2.1+). Same wire-codec caveat as `VarUint`.
+    VarInt(usize),
 }
 
 impl fmt::Display for ParamType {
@@ -92,6 +108,11 @@ impl ParamType {
             ParamType::Time => format!("time"),
             ParamType::Expire => format!("expire"),
             ParamType::PublicKey => format!("pubkey"),
+            ParamType::String => "string".to_owned(),
+            ParamType::Optional(ref param_type) => format!("optional({})", param_type.type_signature()),
+            ParamType::Ref(ref param_type) => format!("ref({})", param_type.type_signature()),
+            ParamType::VarUint(size) => format!("varuint{}", size),
+            ParamType::VarInt(size) => format!("varint{}", size),
         }
     }
 
@@ -113,7 +134,13 @@ impl ParamType {
             ParamType::Map(_, value_type) => {
                 value_type.set_components(components)
             }
-            _ => { 
+            ParamType::Optional(param_type) => {
+                param_type.set_components(components)
+            }
+            ParamType::Ref(param_type) => {
+                param_type.set_components(components)
+            }
+            _ => {
                 if components.len() != 0 {
                     Err(error!(AbiError::UnusedComponents))
                 } else {
@@ -128,6 +155,7 @@ impl ParamType {
         match self {
             ParamType::Uint(size) => *size,
             ParamType::Int(size) => *size,
+            ParamType::VarUint(size) | ParamType::VarInt(size) => *size * 8,
             _ => 0
         }
     }
@@ -136,17 +164,376 @@ impl ParamType {
     pub fn is_supported(&self, abi_version: u8) -> bool {
         match self {
             ParamType::Time | ParamType::Expire | ParamType::PublicKey => abi_version >= 2,
+            ParamType::String | ParamType::VarUint(_) | ParamType::VarInt(_) => abi_version >= 2,
+            ParamType::Optional(param_type) | ParamType::Ref(param_type) =>
+                abi_version >= 2 && param_type.is_supported(abi_version),
+            ParamType::Tuple(params) => params.iter().all(|p| p.kind.is_supported(abi_version)),
+            ParamType::Array(param_type) | ParamType::FixedArray(param_type, _) =>
+                param_type.is_supported(abi_version),
+            ParamType::Map(key_type, value_type) =>
+                key_type.is_supported(abi_version) && value_type.is_supported(abi_version),
             _ => abi_version >= 1
         }
     }
 
+    /// Returns the tuple fields attached via `set_components`, recursing
+    /// into arrays/maps/optional/ref the same way `set_components` does.
+    /// Empty for leaf types and for tuples that have none yet.
+    pub fn components(&self) -> Vec<Param> {
+        match self {
+            ParamType::Tuple(params) => params.clone(),
+            ParamType::Array(param_type) | ParamType::FixedArray(param_type, _) => param_type.components(),
+            ParamType::Map(_, value_type) => value_type.components(),
+            ParamType::Optional(param_type) | ParamType::Ref(param_type) => param_type.components(),
+            _ => vec![],
+        }
+    }
+
     pub fn get_map_key_size(&self) -> Result<usize> {
         match self {
             ParamType::Int(size) | ParamType::Uint(size) => Ok(*size),
             ParamType::Address => Ok(crate::token::STD_ADDRESS_BIT_LENGTH),
-            _ => Err(error!(AbiError::InvalidData { 
-                msg: "Only integer and std address values can be map keys".to_owned() 
+            _ => Err(error!(AbiError::InvalidData {
+                msg: "Only integer and std address values can be map keys".to_owned()
             }))
         }
     }
+
+    /// Parses a type signature (in the grammar produced by `type_signature`)
+    /// into a `ParamType`. The bare `tuple` keyword yields a tuple with no
+    /// components - attach them with `set_components` once the surrounding
+    /// JSON `components` array is available; an inline `(T1,T2,...)` tuple
+    /// gets components named `value0`, `value1`, ...
+    fn read_type(s: &str) -> Result<ParamType> {
+        let s = s.trim();
+
+        // Trailing array suffix: `T[]` or `T[k]`.
+        if s.ends_with(']') {
+            let open = Self::find_matching_bracket(s)?;
+            let item_type = Box::new(Self::read_type(&s[..open])?);
+            let count = &s[open + 1..s.len() - 1];
+            return if count.is_empty() {
+                Ok(ParamType::Array(item_type))
+            } else {
+                let size = count.parse::<usize>().map_err(|_| error!(AbiError::InvalidData {
+                    msg: format!("invalid array size in type `{}`", s)
+                }))?;
+                Ok(ParamType::FixedArray(item_type, size))
+            };
+        }
+
+        // Tuple: `(T1,T2,...)`.
+        if s.starts_with('(') {
+            if !s.ends_with(')') {
+                return Err(error!(AbiError::InvalidData {
+                    msg: format!("unbalanced parentheses in type `{}`", s)
+                }));
+            }
+            let params = Self::split_top_level(&s[1..s.len() - 1])?
+                .into_iter()
+                .enumerate()
+                .map(|(i, sig)| Ok(Param { name: format!("value{}", i), kind: Self::read_type(sig)? }))
+                .collect::<Result<Vec<_>>>()?;
+            return Ok(ParamType::Tuple(params));
+        }
+
+        // `map(K,V)`.
+        if s.starts_with("map(") && s.ends_with(')') {
+            let parts = Self::split_top_level(&s[4..s.len() - 1])?;
+            if parts.len() != 2 {
+                return Err(error!(AbiError::InvalidData {
+                    msg: format!("invalid map type `{}`", s)
+                }));
+            }
+            let key_type = Box::new(Self::read_type(parts[0])?);
+            let value_type = Box::new(Self::read_type(parts[1])?);
+            return Ok(ParamType::Map(key_type, value_type));
+        }
+
+        // `optional(T)`.
+        if s.starts_with("optional(") && s.ends_with(')') {
+            let inner = Self::read_type(&s[9..s.len() - 1])?;
+            return Ok(ParamType::Optional(Box::new(inner)));
+        }
+
+        // `ref(T)`.
+        if s.starts_with("ref(") && s.ends_with(')') {
+            let inner = Self::read_type(&s[4..s.len() - 1])?;
+            return Ok(ParamType::Ref(Box::new(inner)));
+        }
+
+        Ok(match s {
+            "bool" => ParamType::Bool,
+            "cell" => ParamType::Cell,
+            "address" => ParamType::Address,
+            "bytes" => ParamType::Bytes,
+            "gram" => ParamType::Gram,
+            "time" => ParamType::Time,
+            "expire" => ParamType::Expire,
+            "pubkey" => ParamType::PublicKey,
+            "string" => ParamType::String,
+            "unknown" => ParamType::Unknown,
+            // Struct-typed params are encoded as the bare keyword `tuple` in
+            // real ABI JSON, with the field types supplied out-of-band via
+            // the surrounding `components` array - leave it empty here and
+            // let `set_components` fill it in.
+            "tuple" => ParamType::Tuple(vec![]),
+            _ if s.starts_with("varuint") => ParamType::VarUint(Self::parse_size(&s[7..], s)?),
+            _ if s.starts_with("varint") => ParamType::VarInt(Self::parse_size(&s[6..], s)?),
+            _ if s.starts_with("uint") => ParamType::Uint(Self::parse_size(&s[4..], s)?),
+            _ if s.starts_with("int") => ParamType::Int(Self::parse_size(&s[3..], s)?),
+            _ if s.starts_with("fixedbytes") => ParamType::FixedBytes(Self::parse_size(&s[10..], s)?),
+            _ => return Err(error!(AbiError::InvalidData { msg: format!("unknown type `{}`", s) })),
+        })
+    }
+
+    fn parse_size(digits: &str, whole: &str) -> Result<usize> {
+        digits.parse::<usize>().map_err(|_| error!(AbiError::InvalidData {
+            msg: format!("invalid type `{}`", whole)
+        }))
+    }
+
+    /// Finds the index of the `[` matching the trailing `]` of `s`.
+    fn find_matching_bracket(s: &str) -> Result<usize> {
+        let bytes = s.as_bytes();
+        let mut depth = 0i32;
+        for i in (0..bytes.len()).rev() {
+            match bytes[i] {
+                b']' => depth += 1,
+                b'[' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Ok(i);
+                    }
+                }
+                _ => {}
+            }
+        }
+        Err(error!(AbiError::InvalidData { msg: format!("unbalanced brackets in type `{}`", s) }))
+    }
+
+    /// Splits a comma-separated list of sub-signatures, ignoring commas
+    /// nested inside parentheses or brackets.
+    fn split_top_level(s: &str) -> Result<Vec<&str>> {
+        if s.is_empty() {
+            return Ok(vec![]);
+        }
+        let mut parts = vec![];
+        let mut depth = 0i32;
+        let mut start = 0;
+        for (i, c) in s.char_indices() {
+            match c {
+                '(' | '[' => depth += 1,
+                ')' | ']' => depth -= 1,
+                ',' if depth == 0 => {
+                    parts.push(&s[start..i]);
+                    start = i + 1;
+                }
+                _ => {}
+            }
+        }
+        if depth != 0 {
+            return Err(error!(AbiError::InvalidData { msg: format!("unbalanced parentheses in type `{}`", s) }));
+        }
+        parts.push(&s[start..]);
+        Ok(parts)
+    }
+}
+
+impl FromStr for ParamType {
+    type Err = failure::Error;
+
+    /// Parses the textual form produced by `type_signature` back into a
+    /// `ParamType`, making the two round-trippable.
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        ParamType::read_type(s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roundtrip(signature: &str, expected: ParamType) {
+        let parsed = signature.parse::<ParamType>().unwrap();
+        assert_eq!(parsed, expected);
+        assert_eq!(parsed.type_signature(), signature);
+    }
+
+    #[test]
+    fn parses_scalars() {
+        roundtrip("bool", ParamType::Bool);
+        roundtrip("uint256", ParamType::Uint(256));
+        roundtrip("int8", ParamType::Int(8));
+        roundtrip("address", ParamType::Address);
+        roundtrip("fixedbytes4", ParamType::FixedBytes(4));
+    }
+
+    #[test]
+    fn parses_arrays() {
+        roundtrip("uint8[]", ParamType::Array(Box::new(ParamType::Uint(8))));
+        roundtrip("uint8[][3]", ParamType::FixedArray(
+            Box::new(ParamType::Array(Box::new(ParamType::Uint(8)))), 3,
+        ));
+    }
+
+    #[test]
+    fn parses_map() {
+        roundtrip("map(uint256,address)", ParamType::Map(
+            Box::new(ParamType::Uint(256)), Box::new(ParamType::Address),
+        ));
+    }
+
+    #[test]
+    fn parses_inline_tuple() {
+        roundtrip("(uint8,bool)", ParamType::Tuple(vec![
+            Param { name: "value0".to_owned(), kind: ParamType::Uint(8) },
+            Param { name: "value1".to_owned(), kind: ParamType::Bool },
+        ]));
+    }
+
+    #[test]
+    fn parses_bare_tuple_keyword() {
+        // Struct-typed ABI JSON params use the bare `tuple` keyword with
+        // fields supplied separately via `components`.
+        assert_eq!("tuple".parse::<ParamType>().unwrap(), ParamType::Tuple(vec![]));
+        assert_eq!("tuple[]".parse::<ParamType>().unwrap(),
+            ParamType::Array(Box::new(ParamType::Tuple(vec![]))));
+        assert_eq!("map(uint256,tuple)".parse::<ParamType>().unwrap(),
+            ParamType::Map(Box::new(ParamType::Uint(256)), Box::new(ParamType::Tuple(vec![]))));
+    }
+
+    #[test]
+    fn parses_unknown_keyword() {
+        // `unknown` is `type_signature`'s rendering of `ParamType::Unknown`
+        // (used for params whose type couldn't be determined); it must
+        // parse back to the same variant or the type isn't round-trippable.
+        roundtrip("unknown", ParamType::Unknown);
+    }
+
+    #[test]
+    fn parses_abi_2_1_types() {
+        roundtrip("string", ParamType::String);
+        roundtrip("optional(uint256)", ParamType::Optional(Box::new(ParamType::Uint(256))));
+        roundtrip("ref(cell)", ParamType::Ref(Box::new(ParamType::Cell)));
+        roundtrip("varuint16", ParamType::VarUint(16));
+        roundtrip("varint32", ParamType::VarInt(32));
+    }
+
+    #[test]
+    fn rejects_unknown_type() {
+        assert!("nope".parse::<ParamType>().is_err());
+        assert!("(uint8,bool".parse::<ParamType>().is_err());
+    }
+}
+
+#[cfg(feature = "serde")]
+impl ParamType {
+    /// Builds a `ParamType` from the `type`/`components` pair of a JSON ABI
+    /// entry, e.g. `{"name": "a", "type": "tuple[]", "components": [...]}`.
+    /// `type_str` is parsed with the same grammar `type_signature` produces,
+    /// and `components` is attached via `set_components`.
+    pub fn from_json(type_str: &str, components: Vec<Param>) -> Result<ParamType> {
+        let mut kind = type_str.parse::<ParamType>().map_err(|err| error!(AbiError::InvalidData {
+            msg: err.to_string()
+        }))?;
+        kind.set_components(components)?;
+        Ok(kind)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for ParamType {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(&self.type_signature())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for ParamType {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let signature = String::deserialize(deserializer)?;
+        signature.parse::<ParamType>().map_err(serde::de::Error::custom)
+    }
+}
+
+/// On-disk shape of a `Param` in a TON ABI JSON file: `{"name", "type",
+/// "components"}`, where `type` uses the grammar `type_signature` produces
+/// and `components` carries the tuple fields for `tuple`/`tuple[]`/
+/// `map(...,tuple)` entries.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct JsonParam {
+    name: String,
+    #[serde(rename = "type")]
+    type_: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    components: Vec<Param>,
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Param {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        JsonParam {
+            name: self.name.clone(),
+            type_: self.kind.type_signature(),
+            components: self.kind.components(),
+        }.serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Param {
+    /// Parses the `type` string with the same grammar `type_signature`
+    /// produces (reusing `FromStr`) and, when it resolves to a
+    /// `Tuple`/`Array`/`FixedArray`/`Map` whose element is a tuple,
+    /// attaches `components` via `set_components`.
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let raw = JsonParam::deserialize(deserializer)?;
+        let kind = ParamType::from_json(&raw.type_, raw.components).map_err(serde::de::Error::custom)?;
+        Ok(Param { name: raw.name, kind })
+    }
+}
+
+#[cfg(all(test, feature = "serde"))]
+mod serde_tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_scalar_param() {
+        let json = r#"{"name":"value","type":"uint256"}"#;
+        let param: Param = serde_json::from_str(json).unwrap();
+        assert_eq!(param.name, "value");
+        assert_eq!(param.kind, ParamType::Uint(256));
+        assert_eq!(serde_json::to_string(&param).unwrap(), json);
+    }
+
+    #[test]
+    fn round_trips_tuple_param_with_components() {
+        let json = r#"{"name":"pair","type":"tuple","components":[{"name":"a","type":"uint8"},{"name":"b","type":"bool"}]}"#;
+        let param: Param = serde_json::from_str(json).unwrap();
+        assert_eq!(param.kind, ParamType::Tuple(vec![
+            Param { name: "a".to_owned(), kind: ParamType::Uint(8) },
+            Param { name: "b".to_owned(), kind: ParamType::Bool },
+        ]));
+        assert_eq!(serde_json::to_string(&param).unwrap(), json);
+    }
+
+    #[test]
+    fn rejects_tuple_without_components() {
+        let json = r#"{"name":"pair","type":"tuple"}"#;
+        assert!(serde_json::from_str::<Param>(json).is_err());
+    }
 }
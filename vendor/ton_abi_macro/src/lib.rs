@@ -0,0 +1,276 @@
+/*
+* Copyright 2018-2020 TON DEV SOLUTIONS LTD.
+*
+* Licensed under the SOFTWARE EVALUATION License (the "License"); you may not use
+* this file except in compliance with the License.
+*
+* Unless required by applicable law or agreed to in writing, software
+* distributed under the License is distributed on an "AS IS" BASIS,
+* WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+* See the License for the specific TON DEV software governing permissions and
+* limitations under the License.
+*/
+
+//! `abi!` procedural macro.
+//!
+//! Expands a contract ABI JSON file (or inline JSON) into typed Rust
+//! request/reply structs and `encode`/`decode` methods at compile time,
+//! the same way `sol!` turns a Solidity interface into typed bindings.
+//! The expansion parses the ABI once into `Param`/`ParamType` (reusing
+//! `ton_abi`'s serde/`FromStr` support) and builds `encode`/`decode` on
+//! `ton_abi::codegen::AbiValue` for the field types that
+//! `ton_abi::codegen::is_wire_supported` covers, so there is no runtime
+//! JSON parsing or string matching on the hot path. `ParamType::bit_len`
+//! is not used by the expansion.
+
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::parse::{Parse, ParseStream};
+use syn::{LitBool, LitStr, Token};
+
+/// Input accepted by `abi!`: a bare string literal, always read as a path,
+/// or `key = "..."` with an optional `, emit_signature = true`, where the
+/// key `json` gives inline JSON and any other key gives a path.
+struct AbiInput {
+    source: AbiSource,
+    emit_signature: bool,
+}
+
+enum AbiSource {
+    /// Path to an `.abi.json` file, relative to `CARGO_MANIFEST_DIR`.
+    Path(String),
+    /// ABI JSON given inline as a string literal.
+    Inline(String),
+}
+
+impl Parse for AbiInput {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        if input.peek(syn::Ident) {
+            let ident: syn::Ident = input.parse()?;
+            input.parse::<Token![=]>()?;
+            let value: LitStr = input.parse()?;
+            let source = if ident == "json" {
+                AbiSource::Inline(value.value())
+            } else {
+                AbiSource::Path(value.value())
+            };
+
+            let mut emit_signature = false;
+            if input.parse::<Option<Token![,]>>()?.is_some() && !input.is_empty() {
+                let flag_ident: syn::Ident = input.parse()?;
+                input.parse::<Token![=]>()?;
+                let flag: LitBool = input.parse()?;
+                if flag_ident == "emit_signature" {
+                    emit_signature = flag.value;
+                }
+            }
+            return Ok(AbiInput { source, emit_signature });
+        }
+
+        let lit: LitStr = input.parse()?;
+        Ok(AbiInput { source: AbiSource::Path(lit.value()), emit_signature: false })
+    }
+}
+
+/// Expands a contract ABI, given at compile time as a path or inline JSON,
+/// into typed Rust bindings: a request struct and a reply struct per
+/// function, with `encode`/`decode` methods. Pass `emit_signature = true`
+/// to also emit `SIGNATURE`, the raw `ParamType::type_signature` string,
+/// as an associated const on each request struct.
+#[proc_macro]
+pub fn abi(input: TokenStream) -> TokenStream {
+    let input = syn::parse_macro_input!(input as AbiInput);
+
+    let json = match input.source {
+        AbiSource::Inline(json) => json,
+        AbiSource::Path(path) => {
+            let root = std::env::var("CARGO_MANIFEST_DIR").unwrap_or_default();
+            match std::fs::read_to_string(std::path::Path::new(&root).join(&path)) {
+                Ok(contents) => contents,
+                Err(err) => return compile_error(&format!("abi!: failed to read `{}`: {}", path, err)),
+            }
+        }
+    };
+
+    let contract: ton_abi::Contract = match serde_json::from_str(&json) {
+        Ok(contract) => contract,
+        Err(err) => return compile_error(&format!("abi!: invalid ABI JSON: {}", err)),
+    };
+
+    let functions = contract.functions()
+        .values()
+        .map(|function| generate_function(function, input.emit_signature))
+        .collect::<Result<Vec<TokenStream2>, TokenStream>>();
+
+    match functions {
+        Ok(functions) => quote! { #(#functions)* }.into(),
+        Err(err) => err,
+    }
+}
+
+fn compile_error(message: &str) -> TokenStream {
+    syn::Error::new(proc_macro2::Span::call_site(), message).to_compile_error().into()
+}
+
+/// Builds the `encode`/`decode` method bodies for a function. When every
+/// field's `ParamType` is covered by `ton_abi::codegen::is_wire_supported`,
+/// these are real bodies built on `ton_abi::codegen::AbiValue` - no
+/// runtime JSON parsing or string matching. Otherwise they are an honest
+/// `Err`, never a panic, naming the function so the gap is visible at the
+/// call site instead of a silent `unimplemented!()`.
+fn generate_function(function: &ton_abi::Function, emit_signature: bool) -> Result<TokenStream2, TokenStream> {
+    let name = &function.name;
+    let request_name = format_ident!("{}Request", to_camel_case(&function.name));
+    let reply_name = format_ident!("{}Reply", to_camel_case(&function.name));
+
+    let mut tuples = String::new();
+    let request_fields = fields_for(name, &function.inputs, &mut tuples)?;
+    let reply_fields = fields_for(name, &function.outputs, &mut tuples)?;
+    let tuple_structs = parse_tuple_structs(&tuples)?;
+
+    let signature_const = if emit_signature {
+        let signature = function.inputs.iter()
+            .map(|p| p.kind.type_signature())
+            .collect::<Vec<_>>()
+            .join(",");
+        quote! {
+            impl #request_name {
+                /// Raw ABI signature, as produced by `ParamType::type_signature`.
+                pub const SIGNATURE: &'static str = #signature;
+            }
+        }
+    } else {
+        quote! {}
+    };
+
+    let encode_body = if function.inputs.iter().all(|p| ton_abi::codegen::is_wire_supported(&p.kind)) {
+        let field_names = function.inputs.iter().map(|p| format_ident!("{}", p.name)).collect::<Vec<_>>();
+        quote! {
+            let mut buf = Vec::new();
+            #( ton_abi::codegen::AbiValue::abi_encode(&self.#field_names, &mut buf); )*
+            Ok(buf)
+        }
+    } else {
+        quote! {
+            Err(failure::format_err!(concat!(#name, ": a parameter type is not wired to the wire codec yet")))
+        }
+    };
+
+    let decode_body = if function.outputs.iter().all(|p| ton_abi::codegen::is_wire_supported(&p.kind)) {
+        let field_names = function.outputs.iter().map(|p| format_ident!("{}", p.name)).collect::<Vec<_>>();
+        quote! {
+            let mut pos = 0usize;
+            #( let #field_names = ton_abi::codegen::AbiValue::abi_decode(data, &mut pos)
+                .map_err(|err| failure::format_err!("{}", err))?; )*
+            Ok(#reply_name { #(#field_names),* })
+        }
+    } else {
+        quote! {
+            let _ = data;
+            Err(failure::format_err!(concat!(#name, ": a parameter type is not wired to the wire codec yet")))
+        }
+    };
+
+    Ok(quote! {
+        #(#tuple_structs)*
+
+        #[derive(Debug, Clone, PartialEq)]
+        pub struct #request_name {
+            #(#request_fields),*
+        }
+
+        #[derive(Debug, Clone, PartialEq)]
+        pub struct #reply_name {
+            #(#reply_fields),*
+        }
+
+        impl #request_name {
+            pub fn encode(&self) -> ton_abi::Result<Vec<u8>> {
+                #encode_body
+            }
+        }
+
+        impl #reply_name {
+            pub fn decode(data: &[u8]) -> ton_abi::Result<Self> {
+                #decode_body
+            }
+        }
+
+        #signature_const
+    })
+}
+
+/// Maps each `Param` to a named struct field, threading `tuples` through
+/// `rust_type` so that `Tuple`-shaped params accumulate their generated
+/// sub-struct definitions in the caller's buffer instead of discarding them.
+fn fields_for(function: &str, params: &[ton_abi::Param], tuples: &mut String) -> Result<Vec<TokenStream2>, TokenStream> {
+    params.iter().map(|param| {
+        let name = format_ident!("{}", param.name);
+        let ty_str = ton_abi::codegen::rust_type(function, &param.kind, tuples)
+            .map_err(|err| compile_error(&err.to_string()))?;
+        let ty: syn::Type = syn::parse_str(&ty_str)
+            .map_err(|_| compile_error(&format!("abi!: `{}` is not a valid Rust type", ty_str)))?;
+        Ok(quote! { pub #name: #ty })
+    }).collect()
+}
+
+/// Parses the struct definitions `rust_type` accumulated for `Tuple` params
+/// into tokens so `generate_function` can splice them alongside the
+/// request/reply structs; otherwise those tuple types would be referenced
+/// but never declared.
+fn parse_tuple_structs(tuples: &str) -> Result<Vec<TokenStream2>, TokenStream> {
+    if tuples.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    let file: syn::File = syn::parse_str(tuples)
+        .map_err(|_| compile_error("abi!: generated tuple struct definitions are not valid Rust"))?;
+    Ok(file.items.into_iter().map(|item| quote! { #item }).collect())
+}
+
+fn to_camel_case(name: &str) -> String {
+    name.split('_')
+        .map(|part| {
+            let mut chars = part.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().collect::<String>() + chars.as_str(),
+                None => String::new(),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn camel_cases_snake_case_function_names() {
+        assert_eq!(to_camel_case("transfer_tokens"), "TransferTokens");
+        assert_eq!(to_camel_case("send"), "Send");
+    }
+
+    #[test]
+    fn fields_for_accumulates_tuple_structs_across_inputs_and_outputs() {
+        // Regression test: `fields_for` used to own a fresh `tuples` buffer
+        // per call, so any struct a `Tuple` param generated was discarded
+        // before `generate_function` could splice it in, leaving the field's
+        // type referenced but never declared.
+        let mut tuples = String::new();
+        let tuple_param = ton_abi::Param {
+            name: "dest".to_owned(),
+            kind: ton_abi::ParamType::Tuple(vec![
+                ton_abi::Param { name: "workchain".to_owned(), kind: ton_abi::ParamType::Int(8) },
+            ]),
+        };
+
+        fields_for("transfer", std::slice::from_ref(&tuple_param), &mut tuples).unwrap();
+        fields_for("transfer", &[], &mut tuples).unwrap();
+
+        assert!(tuples.contains("pub struct TransferDest"));
+        let structs = parse_tuple_structs(&tuples).unwrap();
+        assert_eq!(structs.len(), 1);
+    }
+}